@@ -0,0 +1,164 @@
+use std::{collections::HashMap, path::Path, process::Command, time::Duration};
+
+use serde::Deserialize;
+
+const DEFAULT_HIDE_DELAY_MS: u64 = 1000;
+const DEFAULT_REVEAL_DELAY_MS: u64 = 0;
+const DEFAULT_HOVER_BUFFER_FRACTION: f32 = 2.0 / 3.0;
+const DEFAULT_TOGGLE_SIGNAL: &str = "SIGUSR1";
+
+/// How a layer's visibility is actually toggled: either a signal sent via
+/// `pkill -<signal> <namespace>` (the original, hardcoded behavior) or an
+/// arbitrary shell command with `{namespace}` substituted, for bars that
+/// don't respond to signals.
+#[derive(Debug, Clone)]
+pub enum ToggleAction {
+    Signal(String),
+    Command(String),
+}
+
+impl ToggleAction {
+    pub fn run(&self, namespace: &str) -> anyhow::Result<()> {
+        match self {
+            ToggleAction::Signal(signal) => {
+                Command::new("pkill")
+                    .args([format!("-{signal}"), namespace.to_string()])
+                    .spawn()?;
+            }
+            ToggleAction::Command(template) => {
+                let command = template.replace("{namespace}", namespace);
+                Command::new("sh").args(["-c", &command]).spawn()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Resolved, per-namespace tuning for `Layer::toggle_visibility` and
+/// `Layer::does_contain_cursor`, with the project's original hardcoded
+/// values as defaults.
+#[derive(Debug, Clone)]
+pub struct LayerSettings {
+    pub hide_delay: Duration,
+    pub reveal_delay: Duration,
+    pub hover_buffer_fraction: f32,
+    pub toggle: ToggleAction,
+    /// Whether this namespace should be force-revealed while a special
+    /// (scratchpad) workspace is open on its monitor.
+    pub reveal_on_special: bool,
+}
+
+impl Default for LayerSettings {
+    fn default() -> Self {
+        Self {
+            hide_delay: Duration::from_millis(DEFAULT_HIDE_DELAY_MS),
+            reveal_delay: Duration::from_millis(DEFAULT_REVEAL_DELAY_MS),
+            hover_buffer_fraction: DEFAULT_HOVER_BUFFER_FRACTION,
+            toggle: ToggleAction::Signal(DEFAULT_TOGGLE_SIGNAL.to_string()),
+            reveal_on_special: false,
+        }
+    }
+}
+
+/// The `toggle` table/value in a `[[layer]]` entry. A bare string is a
+/// signal name (e.g. `toggle = "SIGUSR1"`); a shell command must be spelled
+/// out explicitly as `toggle = { command = "..." }` so it can never be
+/// mistaken for a signal name, regardless of what the string looks like.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+enum ToggleConfig {
+    Signal(String),
+    Command { command: String },
+}
+
+impl From<ToggleConfig> for ToggleAction {
+    fn from(toggle: ToggleConfig) -> Self {
+        match toggle {
+            ToggleConfig::Signal(signal) => ToggleAction::Signal(signal),
+            ToggleConfig::Command { command } => ToggleAction::Command(command),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct LayerConfigEntry {
+    namespace: String,
+    hide_delay_ms: Option<u64>,
+    reveal_delay_ms: Option<u64>,
+    hover_buffer_fraction: Option<f32>,
+    toggle: Option<ToggleConfig>,
+    reveal_on_special: Option<bool>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct RawConfig {
+    #[serde(rename = "layer", default)]
+    layers: Vec<LayerConfigEntry>,
+}
+
+/// Per-namespace settings loaded from
+/// `~/.config/hyprland-autohide-layers/config.toml`. Namespaces with no
+/// `[[layer]]` entry fall back to `LayerSettings::default`.
+#[derive(Debug, Default)]
+pub struct Config {
+    by_namespace: HashMap<String, LayerSettings>,
+}
+
+impl Config {
+    /// Loads the config file at `path`. A missing file is not an error and
+    /// yields an empty config, since every setting has a sensible default.
+    pub fn load(path: &Path) -> anyhow::Result<Config> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Config::default())
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let raw: RawConfig = toml::from_str(&contents)?;
+        let by_namespace = raw
+            .layers
+            .into_iter()
+            .map(|entry| {
+                let defaults = LayerSettings::default();
+                let settings = LayerSettings {
+                    hide_delay: entry
+                        .hide_delay_ms
+                        .map(Duration::from_millis)
+                        .unwrap_or(defaults.hide_delay),
+                    reveal_delay: entry
+                        .reveal_delay_ms
+                        .map(Duration::from_millis)
+                        .unwrap_or(defaults.reveal_delay),
+                    hover_buffer_fraction: entry
+                        .hover_buffer_fraction
+                        .unwrap_or(defaults.hover_buffer_fraction),
+                    toggle: entry
+                        .toggle
+                        .map(ToggleAction::from)
+                        .unwrap_or(defaults.toggle),
+                    reveal_on_special: entry
+                        .reveal_on_special
+                        .unwrap_or(defaults.reveal_on_special),
+                };
+                (entry.namespace, settings)
+            })
+            .collect();
+
+        Ok(Config { by_namespace })
+    }
+
+    /// Namespaces explicitly declared in the config file, to be merged with
+    /// whatever is passed via `--namespace`.
+    pub fn namespaces(&self) -> impl Iterator<Item = &String> {
+        self.by_namespace.keys()
+    }
+
+    pub fn settings_for(&self, namespace: &str) -> LayerSettings {
+        self.by_namespace
+            .get(namespace)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
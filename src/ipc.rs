@@ -0,0 +1,68 @@
+use std::{
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+    thread,
+    time::Duration,
+};
+
+const MAX_ATTEMPTS: u32 = 5;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(200);
+
+/// Resolves the Hyprland IPC socket paths from the current environment.
+/// Called fresh on every (re)connect so a compositor restart, which hands
+/// out a new `HYPRLAND_INSTANCE_SIGNATURE`, is picked up instead of wedging
+/// against a stale path.
+pub fn socket_paths() -> anyhow::Result<(String, String)> {
+    let xdg_runtime_dir = std::env::var("XDG_RUNTIME_DIR")?;
+    let signature = std::env::var("HYPRLAND_INSTANCE_SIGNATURE")?;
+    let base = format!("{xdg_runtime_dir}/hypr/{signature}");
+    Ok((format!("{base}/.socket.sock"), format!("{base}/.socket2.sock")))
+}
+
+/// Connects to `socket`, retrying with exponential backoff instead of
+/// panicking on a transient error (compositor restart, socket not up yet).
+fn connect_with_retry(socket: &str) -> anyhow::Result<UnixStream> {
+    let mut backoff = INITIAL_BACKOFF;
+    let mut last_err = None;
+
+    for attempt in 0..MAX_ATTEMPTS {
+        match UnixStream::connect(socket) {
+            Ok(stream) => return Ok(stream),
+            Err(err) => {
+                last_err = Some(err);
+                if attempt + 1 < MAX_ATTEMPTS {
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                }
+            }
+        }
+    }
+
+    Err(last_err.expect("loop always attempts at least once").into())
+}
+
+/// Sends a one-shot query (e.g. `j/layers`, `j/cursorpos`) and returns the
+/// response. Connecting is retried with backoff; an I/O error while writing
+/// the command or reading the response is returned without a retry.
+pub fn query(socket: &str, command: &[u8]) -> anyhow::Result<String> {
+    let mut stream = connect_with_retry(socket)?;
+    stream.write_all(command)?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    Ok(response)
+}
+
+/// Opens a long-lived connection (the event socket), reconnecting with
+/// backoff on a transient I/O error.
+pub fn connect(socket: &str) -> anyhow::Result<UnixStream> {
+    connect_with_retry(socket)
+}
+
+/// Path to this daemon's own control socket, used to accept newline-delimited
+/// `pin <namespace>` / `unpin <namespace>` messages (see
+/// `spawn_control_listener`) over a Unix socket, not a named pipe or a
+/// signal. Lives alongside the Hyprland sockets under `XDG_RUNTIME_DIR`.
+pub fn control_socket_path() -> anyhow::Result<String> {
+    let xdg_runtime_dir = std::env::var("XDG_RUNTIME_DIR")?;
+    Ok(format!("{xdg_runtime_dir}/hyprland-autohide-layers.control.sock"))
+}
@@ -1,21 +1,58 @@
-use core::time;
+mod config;
+mod ipc;
+
 use std::{
-    collections::HashMap,
-    io::{Read, Write},
-    os::unix::net::UnixStream,
-    process::Command,
-    sync::{Arc, Condvar, Mutex, RwLock},
-    thread::{self, sleep},
+    collections::{HashMap, HashSet},
+    io::{BufRead, BufReader},
+    os::unix::net::UnixListener,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex, RwLock,
+    },
+    thread,
+    time::Duration,
 };
 
 use clap::Parser;
 use serde::Deserialize;
 
+use config::{Config, LayerSettings};
+
+/// How long to wait before reattaching after the event socket drops
+/// (compositor restart, transient I/O error).
+const RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Wakes every layer worker, e.g. after a pin/unpin, a special-workspace
+/// change, or a retirement. The counter is the actual wait predicate (see
+/// `spawn_layer_worker`): bumping it under the lock before `notify_all`
+/// means a wakeup that arrives between a worker's work-block and its next
+/// `wait` is never lost, since the worker re-checks the counter against its
+/// own last-seen value before blocking instead of trusting a one-shot flag.
+type Notifier = (Mutex<u64>, Condvar);
+
+fn notify(notifier: &Notifier) {
+    *notifier.0.lock().unwrap() += 1;
+    notifier.1.notify_all();
+}
+
 #[derive(Parser, Debug)]
 #[command(version, about)]
 struct Opts {
     #[arg(short, long)]
     namespace: Vec<String>,
+
+    /// Path to the TOML config file. Defaults to
+    /// `$XDG_CONFIG_HOME/hyprland-autohide-layers/config.toml`.
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+}
+
+fn default_config_path() -> PathBuf {
+    let config_home = std::env::var("XDG_CONFIG_HOME").unwrap_or_else(|_| {
+        format!("{}/.config", std::env::var("HOME").unwrap_or_default())
+    });
+    PathBuf::from(config_home).join("hyprland-autohide-layers/config.toml")
 }
 
 #[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
@@ -24,20 +61,32 @@ struct CursorPos {
     y: f32,
 }
 
+/// The geometry Hyprland reports for a layer surface, before per-namespace
+/// settings are applied.
 #[derive(Deserialize, Debug, Clone)]
+struct RawLayer {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+    namespace: String,
+}
+
+#[derive(Debug, Clone)]
 struct Layer {
     x: f32,
     y: f32,
     w: f32,
     h: f32,
     namespace: String,
-    #[serde(skip_deserializing)]
+    monitor: Monitor,
     visible: bool,
+    settings: LayerSettings,
 }
 
 impl Layer {
     fn does_contain_cursor(&self, cursorpos: &CursorPos) -> bool {
-        let y_buffer = self.h * 2.0 / 3.0;
+        let y_buffer = self.h * self.settings.hover_buffer_fraction;
         let mut bar_y_max = self.y + self.h;
         let mut bar_y_min = self.y;
 
@@ -62,21 +111,24 @@ impl Layer {
             && cursorpos.x >= bar_x_min
     }
 
-    fn toggle_visibility(&mut self, cursorpos: &CursorPos) -> anyhow::Result<()> {
-        let cursor_over_layer = self.does_contain_cursor(cursorpos);
-        let toggle = || -> anyhow::Result<()> {
-            Command::new("pkill")
-                .args(["-SIGUSR1", &self.namespace])
-                .spawn()?;
-            Ok(())
-        };
+    /// `forced_visible` overrides both the cursor check and suppression: a
+    /// pinned namespace, or one revealed by an open special workspace, stays
+    /// shown regardless of cursor position or fullscreen focus.
+    fn toggle_visibility(
+        &mut self,
+        cursorpos: &CursorPos,
+        suppressed: bool,
+        forced_visible: bool,
+    ) -> anyhow::Result<()> {
+        let cursor_over_layer = forced_visible || (!suppressed && self.does_contain_cursor(cursorpos));
         if cursor_over_layer && !self.visible {
-            toggle()?;
+            thread::sleep(self.settings.reveal_delay);
+            self.settings.toggle.run(&self.namespace)?;
             self.visible = true;
             println!("{} revealed.", self.namespace);
         } else if !cursor_over_layer && self.visible {
-            thread::sleep(time::Duration::from_secs(1));
-            toggle()?;
+            thread::sleep(self.settings.hide_delay);
+            self.settings.toggle.run(&self.namespace)?;
             self.visible = false;
             println!("{} hidden.", self.namespace);
         }
@@ -90,32 +142,35 @@ type Monitor = String;
 
 #[derive(Deserialize, Debug, Clone)]
 struct LayerByLevel {
-    levels: HashMap<Level, Vec<Layer>>,
+    levels: HashMap<Level, Vec<RawLayer>>,
 }
 
-fn get_layers(namespaces: &Vec<String>, socket: &str) -> anyhow::Result<Vec<Layer>> {
-    let mut stream = UnixStream::connect(socket).unwrap();
-    let _ = stream.write(b"j/layers");
-    let mut layers_str = String::new();
-    stream.read_to_string(&mut layers_str).unwrap();
+fn get_layers(namespaces: &[String], socket: &str, config: &Config) -> anyhow::Result<Vec<Layer>> {
+    let layers_str = ipc::query(socket, b"j/layers")?;
     let levels_by_monitor: HashMap<Monitor, LayerByLevel> = serde_json::from_str(&layers_str)?;
 
     Ok(levels_by_monitor
         .into_iter()
-        .flat_map(|(_, layer_by_level)| {
+        .flat_map(|(monitor, layer_by_level)| {
             layer_by_level
                 .levels
-                .into_iter()
-                .flat_map(|(_, layer)| layer)
-                .collect::<Vec<Layer>>()
+                .into_values()
+                .flatten()
+                .map(move |layer| (monitor.clone(), layer))
+                .collect::<Vec<(Monitor, RawLayer)>>()
         })
-        .filter_map(|layer| {
+        .filter_map(|(monitor, layer)| {
             for namespace in namespaces {
                 if *namespace == layer.namespace {
                     return Some(Layer {
+                        x: layer.x,
+                        y: layer.y,
+                        w: layer.w,
+                        h: layer.h,
                         namespace: namespace.to_string(),
+                        monitor: monitor.clone(),
                         visible: true,
-                        ..layer
+                        settings: config.settings_for(namespace),
                     });
                 }
             }
@@ -124,105 +179,566 @@ fn get_layers(namespaces: &Vec<String>, socket: &str) -> anyhow::Result<Vec<Laye
         .collect::<Vec<Layer>>())
 }
 
-fn get_cursor_pos(socket: &str) -> anyhow::Result<CursorPos> {
-    let mut stream = UnixStream::connect(socket).unwrap();
-    let _ = stream.write(b"j/cursorpos");
-    let mut cursorpos_str = String::new();
-    while cursorpos_str.is_empty() {
-        let _ = stream.read_to_string(&mut cursorpos_str);
+/// A running `toggle_visibility` worker for one `Layer` instance, plus the
+/// join handle needed to wait for it to actually exit once stopped.
+struct LayerHandle {
+    stop: Arc<AtomicBool>,
+    join: thread::JoinHandle<()>,
+}
+
+impl LayerHandle {
+    /// Signals the worker to stop. Does not itself wake or join it; callers
+    /// retiring a batch of handles must still notify the shared condvar and
+    /// join afterwards (see `retire_layer`) or the worker may linger.
+    fn signal_stop(&self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Identifies one layer-surface instance: a namespace like `waybar` commonly
+/// runs one surface per monitor, and `openlayer`/`closelayer` only ever name
+/// the namespace, so the monitor is what tells siblings apart.
+type LayerKey = (String, Monitor);
+type LayerRegistry = Mutex<HashMap<LayerKey, Vec<LayerHandle>>>;
+type PinnedSet = Mutex<HashSet<String>>;
+
+fn spawn_layer_worker(
+    mut layer: Layer,
+    cursorpos: Arc<RwLock<CursorPos>>,
+    suppressed_monitor: Arc<RwLock<Option<Monitor>>>,
+    special_monitor: Arc<RwLock<HashSet<Monitor>>>,
+    pinned: Arc<PinnedSet>,
+    notifier: Arc<Notifier>,
+) -> LayerHandle {
+    let stop = Arc::new(AtomicBool::new(false));
+    let worker_stop = Arc::clone(&stop);
+
+    let join = thread::spawn(move || {
+        let mut last_seen = *notifier.0.lock().unwrap();
+
+        loop {
+            if worker_stop.load(Ordering::SeqCst) {
+                return;
+            }
+
+            {
+                let curr_pos = cursorpos.read().unwrap();
+                let suppressed = suppressed_monitor.read().unwrap().as_ref() == Some(&layer.monitor);
+                let revealed_by_special = layer.settings.reveal_on_special
+                    && special_monitor.read().unwrap().contains(&layer.monitor);
+                let forced_visible =
+                    revealed_by_special || pinned.lock().unwrap().contains(&layer.namespace);
+
+                match layer.toggle_visibility(&curr_pos, suppressed, forced_visible) {
+                    Ok(_) => {}
+                    Err(err) => eprintln!("{}", err),
+                }
+            }
+
+            let mut generation = notifier.0.lock().unwrap();
+            while *generation == last_seen && !worker_stop.load(Ordering::SeqCst) {
+                generation = notifier.1.wait(generation).unwrap();
+            }
+            last_seen = *generation;
+        }
+    });
+
+    LayerHandle { stop, join }
+}
+
+/// Retires every worker currently registered for `key`, if any, and joins
+/// their threads before returning. Joining matters on top of the
+/// generation-counter wakeup: without it, a reload could spawn the
+/// replacement worker (in `reconcile_namespace`) before the outgoing one had
+/// actually observed the stop flag, so both would toggle the same bar
+/// concurrently. Signalling all handles before notifying, and notifying
+/// before joining any of them, avoids joining a thread that is still
+/// blocked waiting on a wakeup that hasn't been sent yet.
+fn retire_layer(registry: &LayerRegistry, notifier: &Arc<Notifier>, key: &LayerKey) {
+    if let Some(handles) = registry.lock().unwrap().remove(key) {
+        for handle in &handles {
+            handle.signal_stop();
+        }
+        notify(notifier);
+        for handle in handles {
+            let _ = handle.join.join();
+        }
+    }
+}
+
+/// Retires every currently registered worker, leaving the registry empty.
+/// Used before reattaching after a reconnect so `run` always starts from a
+/// clean slate instead of accumulating workers across compositor restarts.
+fn retire_all(registry: &LayerRegistry, notifier: &Arc<Notifier>) {
+    let keys: Vec<LayerKey> = registry.lock().unwrap().keys().cloned().collect();
+    for key in keys {
+        retire_layer(registry, notifier, &key);
+    }
+}
+
+/// Re-queries `j/layers` for `namespace` and reconciles the registry against
+/// it: surfaces that are no longer present are retired and surfaces that are
+/// new are spawned, but a `(namespace, monitor)` pair that's still present is
+/// left running untouched. This matters because `openlayer`/`closelayer`
+/// only ever name the namespace, not which monitor's instance opened or
+/// closed — a namespace like `waybar` that runs one surface per monitor
+/// would otherwise have every sibling retired whenever just one of them
+/// closed.
+#[allow(clippy::too_many_arguments)]
+fn reconcile_namespace(
+    registry: &LayerRegistry,
+    namespace: &str,
+    ipc_socket: &str,
+    config: &Config,
+    cursorpos: &Arc<RwLock<CursorPos>>,
+    suppressed_monitor: &Arc<RwLock<Option<Monitor>>>,
+    special_monitor: &Arc<RwLock<HashSet<Monitor>>>,
+    pinned: &Arc<PinnedSet>,
+    notifier: &Arc<Notifier>,
+) -> anyhow::Result<()> {
+    let namespaces = vec![namespace.to_string()];
+    let layers = get_layers(&namespaces, ipc_socket, config)?;
+    let current_monitors: HashSet<Monitor> =
+        layers.iter().map(|layer| layer.monitor.clone()).collect();
+
+    let stale_keys: Vec<LayerKey> = registry
+        .lock()
+        .unwrap()
+        .keys()
+        .filter(|(ns, monitor)| ns == namespace && !current_monitors.contains(monitor))
+        .cloned()
+        .collect();
+    for key in &stale_keys {
+        retire_layer(registry, notifier, key);
+    }
+
+    for layer in layers {
+        let key = (namespace.to_string(), layer.monitor.clone());
+        if registry.lock().unwrap().contains_key(&key) {
+            continue;
+        }
+        let handle = spawn_layer_worker(
+            layer,
+            Arc::clone(cursorpos),
+            Arc::clone(suppressed_monitor),
+            Arc::clone(special_monitor),
+            Arc::clone(pinned),
+            Arc::clone(notifier),
+        );
+        registry.lock().unwrap().entry(key).or_default().push(handle);
     }
-    Ok(serde_json::from_str(cursorpos_str.as_str())?)
+
+    Ok(())
 }
 
+fn get_cursor_pos(socket: &str) -> anyhow::Result<CursorPos> {
+    let cursorpos_str = ipc::query(socket, b"j/cursorpos")?;
+    Ok(serde_json::from_str(&cursorpos_str)?)
+}
+
+/// The fields of `j/activewindow` this daemon cares about.
 #[derive(Deserialize, Debug, Clone)]
-struct Client {
-    fullscreen: bool,
+struct ActiveWindow {
+    address: String,
     floating: bool,
-    #[serde(rename = "focusHistoryID")]
-    focus_history_id: u16,
+    fullscreen: bool,
 }
 
-fn fullscreen_or_floating_focused(socket: &str) -> anyhow::Result<bool> {
-    let mut stream = UnixStream::connect(socket).unwrap();
-    let _ = stream.write(b"j/clients");
-    let mut clients_str = String::new();
-    stream.read_to_string(&mut clients_str).unwrap();
-    let clients: Vec<Client> = serde_json::from_str(&clients_str)?;
-
-    // If there aren't any clients, we don't want to
-    // stop the application from functioning
-    if clients.is_empty() {
-        return Ok(false);
+/// Queries the currently focused window directly, instead of trusting it to
+/// be fully reconstructable from event deltas. `j/activewindow` returns `{}`
+/// when nothing is focused.
+fn get_active_window(socket: &str) -> anyhow::Result<Option<ActiveWindow>> {
+    let response = ipc::query(socket, b"j/activewindow")?;
+    if serde_json::from_str::<HashMap<String, serde_json::Value>>(&response)
+        .map(|obj| obj.is_empty())
+        .unwrap_or(false)
+    {
+        return Ok(None);
     }
+    Ok(Some(serde_json::from_str(&response)?))
+}
 
-    Ok(clients
-        .into_iter()
-        .any(|client| client.focus_history_id == 0 && (client.fullscreen || client.floating)))
+/// The fields of `j/monitors` this daemon cares about.
+#[derive(Deserialize, Debug, Clone)]
+struct RawMonitor {
+    name: String,
+    focused: bool,
 }
 
-fn main() {
-    let xdg_runtime_dir = std::env::var("XDG_RUNTIME_DIR").unwrap();
-    let hyprland_instance_signature = std::env::var("HYPRLAND_INSTANCE_SIGNATURE").unwrap();
-    let opts = Opts::parse();
-    let socket_one = format!(
-        "{}/hypr/{}/.socket.sock",
-        xdg_runtime_dir, hyprland_instance_signature
-    );
-    let mut layers = get_layers(&opts.namespace, &socket_one).unwrap();
+/// Queries the currently focused monitor directly, since the event socket
+/// never replays current state on connect: `focusedmon` only fires on the
+/// *next* monitor switch, so on a single-monitor box it never fires at all
+/// and fullscreen/floating suppression would otherwise stay dead for the
+/// entire session.
+fn get_focused_monitor(socket: &str) -> anyhow::Result<Option<Monitor>> {
+    let response = ipc::query(socket, b"j/monitors")?;
+    let monitors: Vec<RawMonitor> = serde_json::from_str(&response)?;
+    Ok(monitors.into_iter().find(|monitor| monitor.focused).map(|monitor| monitor.name))
+}
+
+/// Tracks just enough client state to answer `fullscreen_or_floating_focused`
+/// from event-socket lines instead of polling `j/clients` every tick. Only
+/// the monitor that currently holds focus can be suppressed, so a fullscreen
+/// window on one monitor never silences bars on another.
+#[derive(Debug, Default)]
+struct FocusState {
+    focused_address: Option<String>,
+    focused_fullscreen: bool,
+    focused_monitor: Option<Monitor>,
+    floating_by_address: HashMap<String, bool>,
+    /// Monitors whose special workspace is currently open. A `HashSet`
+    /// because special workspaces are per-monitor and more than one can be
+    /// open at once; a single `Option<Monitor>` would have one open special
+    /// workspace silently overwrite another.
+    special_active_monitors: HashSet<Monitor>,
+}
 
-    while layers.len() != opts.namespace.len() {
-        sleep(std::time::Duration::from_secs(1));
-        layers = get_layers(&opts.namespace, &socket_one).unwrap();
+impl FocusState {
+    fn suppresses(&self) -> bool {
+        if self.focused_fullscreen {
+            return true;
+        }
+        self.focused_address
+            .as_ref()
+            .and_then(|addr| self.floating_by_address.get(addr))
+            .copied()
+            .unwrap_or(false)
     }
 
-    let cursorpos = Arc::new(RwLock::new(get_cursor_pos(&socket_one).unwrap()));
+    /// The monitor layers should be suppressed on, if any.
+    fn suppressed_monitor(&self) -> Option<Monitor> {
+        if self.suppresses() {
+            self.focused_monitor.clone()
+        } else {
+            None
+        }
+    }
 
-    let cursorpos_updater = Arc::clone(&cursorpos);
-    let cursorpos_update_notifier = Arc::new((Mutex::new(false), Condvar::new()));
+    /// Seeds fullscreen/floating state for the newly focused window from a
+    /// `j/activewindow` query, since `activewindowv2` alone doesn't carry
+    /// either: a window that opens floating via a window rule, or a
+    /// fullscreen window re-focused without a trailing `fullscreen` event,
+    /// would otherwise never be recognized as suppressing. Also prunes
+    /// `floating_by_address` down to just the newly focused window, since
+    /// only the focused address is ever read and closed windows otherwise
+    /// leave one stale entry behind for the life of the daemon.
+    fn seed_focus(&mut self, window: Option<ActiveWindow>) {
+        match window {
+            Some(window) => {
+                self.focused_fullscreen = window.fullscreen;
+                self.floating_by_address.clear();
+                self.floating_by_address
+                    .insert(window.address.clone(), window.floating);
+                self.focused_address = Some(window.address);
+            }
+            None => {
+                self.focused_address = None;
+                self.focused_fullscreen = false;
+                self.floating_by_address.clear();
+            }
+        }
+    }
 
-    layers.into_iter().for_each(|mut layer| {
-        let cursorpos = Arc::clone(&cursorpos);
-        let notifier = Arc::clone(&cursorpos_update_notifier);
-        thread::spawn(move || loop {
-            dbg!("Woke up to do update");
-            {
-                let curr_pos = cursorpos.read().unwrap();
+    /// Applies one `EVENT>>DATA` line, returning whether it could change
+    /// suppression or layer visibility and therefore warrants a fresh
+    /// cursor-position query. `activewindowv2` is handled separately by the
+    /// caller via `seed_focus`, since seeding it correctly needs an IPC
+    /// query rather than just the event payload.
+    fn apply(&mut self, event: &str, data: &str) -> bool {
+        match event {
+            "fullscreen" => {
+                self.focused_fullscreen = data.trim() == "1";
+                true
+            }
+            "changefloatingmode" => {
+                if let Some((address, floating)) = data.split_once(',') {
+                    self.floating_by_address
+                        .insert(address.to_string(), floating.trim() == "1");
+                    true
+                } else {
+                    false
+                }
+            }
+            "focusedmon" => {
+                let monitor = data.split(',').next().unwrap_or("").trim();
+                self.focused_monitor = if monitor.is_empty() {
+                    None
+                } else {
+                    Some(monitor.to_string())
+                };
+                true
+            }
+            "activespecial" => {
+                let mut parts = data.split(',');
+                let workspace = parts.next().unwrap_or("").trim();
+                let monitor = parts.next().unwrap_or("").trim();
+                if workspace.is_empty() {
+                    self.special_active_monitors.remove(monitor);
+                } else if !monitor.is_empty() {
+                    self.special_active_monitors.insert(monitor.to_string());
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+}
 
-                match layer.toggle_visibility(&curr_pos) {
-                    Ok(_) => {}
+/// Opens Hyprland's event socket and, for every line relevant to suppression,
+/// wakes up the layer workers and, unless the focused monitor is currently
+/// suppressed, refreshes the cursor position. Also reacts
+/// to `openlayer`/`closelayer` so watched namespaces are picked up or dropped
+/// as they come and go, instead of being fixed at startup. Replaces the old
+/// fixed-interval poll of `j/clients` and `j/cursorpos`.
+#[allow(clippy::too_many_arguments)]
+fn watch_events(
+    event_socket: &str,
+    ipc_socket: &str,
+    namespaces: &[String],
+    config: &Config,
+    registry: &LayerRegistry,
+    cursorpos: &Arc<RwLock<CursorPos>>,
+    suppressed_monitor: &Arc<RwLock<Option<Monitor>>>,
+    special_monitor: &Arc<RwLock<HashSet<Monitor>>>,
+    pinned: &Arc<PinnedSet>,
+    notifier: &Arc<Notifier>,
+) -> anyhow::Result<()> {
+    let stream = ipc::connect(event_socket)?;
+    let reader = BufReader::new(stream);
+    let mut focus = FocusState::default();
+
+    match get_focused_monitor(ipc_socket) {
+        Ok(monitor) => focus.focused_monitor = monitor,
+        Err(err) => eprintln!("{}", err),
+    }
+    match get_active_window(ipc_socket) {
+        Ok(window) => focus.seed_focus(window),
+        Err(err) => eprintln!("{}", err),
+    }
+    *suppressed_monitor.write().unwrap() = focus.suppressed_monitor();
+    notify(notifier);
+
+    for line in reader.lines() {
+        let line = line?;
+        let Some((event, data)) = line.split_once(">>") else {
+            continue;
+        };
+
+        match event {
+            "openlayer" | "closelayer" => {
+                let namespace = data.trim();
+                if namespaces.iter().any(|ns| ns == namespace) {
+                    if let Err(err) = reconcile_namespace(
+                        registry,
+                        namespace,
+                        ipc_socket,
+                        config,
+                        cursorpos,
+                        suppressed_monitor,
+                        special_monitor,
+                        pinned,
+                        notifier,
+                    ) {
+                        eprintln!("{}", err);
+                    }
+                }
+                continue;
+            }
+            "activewindowv2" => {
+                match get_active_window(ipc_socket) {
+                    Ok(window) => focus.seed_focus(window),
                     Err(err) => eprintln!("{}", err),
                 }
             }
-            let lock = notifier.0.lock().unwrap();
-            let _guard = notifier.1.wait(lock).unwrap();
-        });
-    });
-
-    let notifier = Arc::clone(&cursorpos_update_notifier);
+            _ => {
+                if !focus.apply(event, data) {
+                    continue;
+                }
+            }
+        }
 
-    loop {
-        thread::sleep(time::Duration::from_millis(100));
-        dbg!("Checking cursor pos");
+        *suppressed_monitor.write().unwrap() = focus.suppressed_monitor();
+        *special_monitor.write().unwrap() = focus.special_active_monitors.clone();
+        notify(notifier);
 
-        if fullscreen_or_floating_focused(&socket_one).is_ok_and(|res| res) {
+        if focus.suppresses() {
             continue;
         }
 
-        let mut prev_pos = cursorpos_updater.write().unwrap();
-
-        let curr_pos = match get_cursor_pos(&socket_one) {
-            Ok(new_cursorpos) => new_cursorpos,
+        let curr_pos = match get_cursor_pos(ipc_socket) {
+            Ok(pos) => pos,
             Err(err) => {
                 eprintln!("{}", err);
                 continue;
             }
         };
 
+        let mut prev_pos = cursorpos.write().unwrap();
         if *prev_pos == curr_pos {
             continue;
         }
-
         *prev_pos = curr_pos;
-        notifier.1.notify_all();
+        notify(notifier);
+    }
+
+    Ok(())
+}
+
+/// Resolves the current sockets, reattaches every watched namespace from
+/// scratch, and blocks in `watch_events`. Returns on a recoverable I/O
+/// error so the caller can back off and retry instead of crashing on a
+/// compositor restart or transient socket error.
+#[allow(clippy::too_many_arguments)]
+fn run(
+    namespaces: &[String],
+    config: &Config,
+    registry: &LayerRegistry,
+    cursorpos: &Arc<RwLock<CursorPos>>,
+    suppressed_monitor: &Arc<RwLock<Option<Monitor>>>,
+    special_monitor: &Arc<RwLock<HashSet<Monitor>>>,
+    pinned: &Arc<PinnedSet>,
+    notifier: &Arc<Notifier>,
+) -> anyhow::Result<()> {
+    let (ipc_socket, event_socket) = ipc::socket_paths()?;
+
+    retire_all(registry, notifier);
+    *cursorpos.write().unwrap() = get_cursor_pos(&ipc_socket)?;
+    *suppressed_monitor.write().unwrap() = None;
+    special_monitor.write().unwrap().clear();
+
+    let layers = get_layers(namespaces, &ipc_socket, config)?;
+    layers.into_iter().for_each(|layer| {
+        let key = (layer.namespace.clone(), layer.monitor.clone());
+        let handle = spawn_layer_worker(
+            layer,
+            Arc::clone(cursorpos),
+            Arc::clone(suppressed_monitor),
+            Arc::clone(special_monitor),
+            Arc::clone(pinned),
+            Arc::clone(notifier),
+        );
+        registry
+            .lock()
+            .unwrap()
+            .entry(key)
+            .or_default()
+            .push(handle);
+    });
+
+    // Namespaces not yet present at startup are picked up later via
+    // `openlayer` events instead of blocking here until they all appear.
+
+    watch_events(
+        &event_socket,
+        &ipc_socket,
+        namespaces,
+        config,
+        registry,
+        cursorpos,
+        suppressed_monitor,
+        special_monitor,
+        pinned,
+        notifier,
+    )
+}
+
+/// Listens on the control socket for `pin <namespace>` / `unpin <namespace>`
+/// messages and updates the shared pinned set accordingly. Pinned
+/// namespaces are forced visible by every worker until unpinned, useful for
+/// presentations or while dragging windows.
+///
+/// Deliberately a Unix socket rather than a named pipe or a `SIGUSR2`
+/// handler: a socket lets multiple clients connect at once and parses each
+/// line on its own (a malformed line is ignored) instead of one process-wide
+/// signal, without
+/// giving up the "just write a line of text" ergonomics a FIFO would have
+/// offered. Clients write newline-delimited `pin <namespace>` / `unpin
+/// <namespace>` to the path from `ipc::control_socket_path`, not to a FIFO
+/// and not via a signal.
+fn spawn_control_listener(
+    path: String,
+    pinned: Arc<PinnedSet>,
+    notifier: Arc<Notifier>,
+) -> anyhow::Result<()> {
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { continue };
+            let pinned = Arc::clone(&pinned);
+            let notifier = Arc::clone(&notifier);
+            thread::spawn(move || {
+                let reader = BufReader::new(stream);
+                for line in reader.lines().map_while(Result::ok) {
+                    let mut parts = line.split_whitespace();
+                    match (parts.next(), parts.next()) {
+                        (Some("pin"), Some(namespace)) => {
+                            pinned.lock().unwrap().insert(namespace.to_string());
+                        }
+                        (Some("unpin"), Some(namespace)) => {
+                            pinned.lock().unwrap().remove(namespace);
+                        }
+                        _ => continue,
+                    }
+                    notify(&notifier);
+                }
+            });
+        }
+    });
+
+    Ok(())
+}
+
+fn main() {
+    let opts = Opts::parse();
+
+    let config_path = opts.config.clone().unwrap_or_else(default_config_path);
+    let config = Config::load(&config_path).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        Config::default()
+    });
+
+    let mut namespaces = opts.namespace.clone();
+    for namespace in config.namespaces() {
+        if !namespaces.contains(namespace) {
+            namespaces.push(namespace.clone());
+        }
+    }
+
+    let cursorpos = Arc::new(RwLock::new(CursorPos { x: 0.0, y: 0.0 }));
+    let cursorpos_update_notifier: Arc<Notifier> = Arc::new((Mutex::new(0u64), Condvar::new()));
+    let suppressed_monitor: Arc<RwLock<Option<Monitor>>> = Arc::new(RwLock::new(None));
+    let special_monitor: Arc<RwLock<HashSet<Monitor>>> = Arc::new(RwLock::new(HashSet::new()));
+    let pinned: Arc<PinnedSet> = Arc::new(Mutex::new(HashSet::new()));
+    let registry: LayerRegistry = Mutex::new(HashMap::new());
+
+    match ipc::control_socket_path() {
+        Ok(control_path) => {
+            if let Err(err) = spawn_control_listener(
+                control_path,
+                Arc::clone(&pinned),
+                Arc::clone(&cursorpos_update_notifier),
+            ) {
+                eprintln!("{}", err);
+            }
+        }
+        Err(err) => eprintln!("{}", err),
+    }
+
+    loop {
+        if let Err(err) = run(
+            &namespaces,
+            &config,
+            &registry,
+            &cursorpos,
+            &suppressed_monitor,
+            &special_monitor,
+            &pinned,
+            &cursorpos_update_notifier,
+        ) {
+            eprintln!("{} -- reconnecting", err);
+        }
+        thread::sleep(RECONNECT_BACKOFF);
     }
 }